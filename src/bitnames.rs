@@ -2,23 +2,136 @@
  * Utilities for interacting with Bitnames
 */
 
-use std::{net::{IpAddr, Ipv4Addr, Ipv6Addr}, str::FromStr};
+use std::{fmt, net::{IpAddr, Ipv4Addr, Ipv6Addr}, str::FromStr, time::Duration};
 
-use crypto::{sha2::Sha256, digest::Digest};
+use async_trait::async_trait;
+use bitcoin_hashes::{sha256, Hash};
 use rust_decimal::Decimal;
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1};
 use serde::Deserialize;
 use serde_json::{Map as JsonMap, Value as JsonValue};
-use serde_with::{DisplayFromStr, hex::Hex as SerdeWithHex, serde_as};
+use serde_with::{DisplayFromStr, serde_as};
+
+/** decode a lowercase/uppercase hex string into raw bytes */
+fn decode_hex(s: &str) -> anyhow::Result<Vec<u8>> {
+    hex::decode(s).map_err(anyhow::Error::from)
+}
+
+/** A commitment to a [`WebRecord`]'s JCS-canonicalized contents, distinct
+ * from any other bare 32-byte array. */
+#[derive(Clone, Copy, Eq)]
+pub struct Commitment([u8; 32]);
+
+impl Commitment {
+    /** canonicalize `record` and compute its SHA-256 digest as a commitment */
+    fn from_record(record: &JsonMap<String, JsonValue>) -> anyhow::Result<Self> {
+        let canonical_utf8 = serde_jcs::to_vec(record)?;
+        let digest = sha256::Hash::hash(&canonical_utf8);
+        Ok(Self(digest.to_byte_array()))
+    }
+}
+
+impl PartialEq for Commitment {
+    /** constant-time equality, to avoid leaking commitment bytes via timing */
+    fn eq(&self, other: &Self) -> bool {
+        let diff = self.0.iter().zip(other.0.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        diff == 0
+    }
+}
+
+impl fmt::Debug for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Commitment({self})")
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = decode_hex(s)?;
+        bytes.try_into()
+    }
+}
+
+impl AsRef<[u8]> for Commitment {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<u8>> for Commitment {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        let len = bytes.len();
+        let bytes: [u8; 32] = bytes.try_into()
+            .map_err(|_| anyhow::anyhow!("commitment must be exactly 32 bytes, got {len}"))?;
+        Ok(Self(bytes))
+    }
+}
 
 #[serde_as]
 #[derive(Debug, Deserialize)]
 pub struct BitnameInfo {
-    #[serde_as(as = "Option<SerdeWithHex>")]
-    commitment: Option<[u8; 32]>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    commitment: Option<Commitment>,
     #[serde_as(as = "Option<DisplayFromStr>")]
     ip4_addr: Option<Ipv4Addr>,
     #[serde_as(as = "Option<DisplayFromStr>")]
     ip6_addr: Option<Ipv6Addr>,
+    /** the BitName owner's public key, used to verify a resolved
+     * [`WebRecord`]'s signature */
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    owner_pubkey: Option<PublicKey>,
+}
+
+/** The schema version of a [`WebRecord`]'s `"version"` field.
+ *
+ * New variants can be added without breaking older records. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WebRecordVersion {
+    V0_0_1,
+    V0_0_2,
+}
+
+impl WebRecordVersion {
+    /** the most recent version this crate knows how to parse */
+    pub fn latest() -> Self {
+        Self::V0_0_2
+    }
+}
+
+impl fmt::Display for WebRecordVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version_str = match self {
+            Self::V0_0_1 => "0.0.1",
+            Self::V0_0_2 => "0.0.2",
+        };
+        write!(f, "{version_str}")
+    }
+}
+
+impl FromStr for WebRecordVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0.0.1" => Ok(Self::V0_0_1),
+            "0.0.2" => Ok(Self::V0_0_2),
+            _ => anyhow::bail!("unsupported web record version: {s}"),
+        }
+    }
 }
 
 /** A record resolved via a BitName's IP address */
@@ -32,59 +145,354 @@ impl BitnameInfo {
     }
 }
 
+/** path at which a BitName's web record is expected to be served */
+const WEB_RECORD_PATH: &str = "/.well-known/bitname-record.json";
+
+/** Resolves a [`WebRecord`] for a [`BitnameInfo`].
+ *
+ * Trait-object-friendly so tests can inject a mock implementation instead
+ * of hitting the network. */
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    async fn resolve(&self, info: &BitnameInfo) -> anyhow::Result<WebRecord>;
+}
+
+/** Concrete [`Resolver`] that fetches records over HTTPS. */
+pub struct HttpResolver {
+    client: reqwest::Client,
+}
+
+impl HttpResolver {
+    pub fn new() -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl Resolver for HttpResolver {
+    /** Connect to the BitName's resolved IP address (preferring IPv6,
+     * falling back to IPv4), fetch the record document, and validate it
+     * against the expected commitment. */
+    async fn resolve(&self, info: &BitnameInfo) -> anyhow::Result<WebRecord> {
+        let ip_addr = info.ip_addr()
+            .ok_or_else(|| anyhow::anyhow!("BitnameInfo has no resolvable IP address"))?;
+        let host = match ip_addr {
+            IpAddr::V6(ip6_addr) => format!("[{ip6_addr}]"),
+            IpAddr::V4(ip4_addr) => ip4_addr.to_string(),
+        };
+        let url = format!("https://{host}{WEB_RECORD_PATH}");
+        let record: WebRecord = self.client
+            .get(url)
+            .send()
+            .await
+            .map_err(|err| anyhow::anyhow!("failed to connect to {ip_addr}: {err}"))?
+            .error_for_status()?
+            .json()
+            .await
+            .map_err(|err| anyhow::anyhow!("failed to parse web record from {ip_addr}: {err}"))?;
+        record.validate(info.commitment.as_ref(), info.owner_pubkey.as_ref())?;
+        Ok(record)
+    }
+}
+
 impl WebRecord {
-    pub fn version_ok(&self) -> bool {
+    /** the schema version this record declares, if it is known */
+    pub fn version(&self) -> Option<WebRecordVersion> {
         match self.0.get("version") {
-            Some(JsonValue::String(version_string)) =>
-                version_string == "0.0.1", 
-            Some(_) | None => false,
+            Some(JsonValue::String(version_string)) => version_string.parse().ok(),
+            Some(_) | None => None,
         }
     }
 
+    pub fn version_ok(&self) -> bool {
+        self.version().is_some()
+    }
+
     /** canonicalize and compute the sha-256 digest as a commitment */
-    pub fn commitment(&self) -> [u8; 32] {
-        let mut hasher = Sha256::new();
-        let canonical_utf8 = serde_jcs::to_vec(&self.0).unwrap();
-        hasher.input(&canonical_utf8);
-        let mut res = [0u8; 32];
-        hasher.result(&mut res);
-        res
+    pub fn commitment(&self) -> anyhow::Result<Commitment> {
+        Commitment::from_record(&self.0)
     }
 
-    pub fn commitment_ok(&self, expected: &[u8; 32]) -> bool {
-        self.commitment() == *expected
+    pub fn commitment_ok(&self, expected: &Commitment) -> anyhow::Result<bool> {
+        Ok(self.commitment()? == *expected)
     }
 
-    pub fn validate(&self, expected_commitment: Option<&[u8; 32]>)
-        -> anyhow::Result<()> {
-        if !self.version_ok() {
-            anyhow::bail!("version number missing or unsupported")
-        };
+    /** validate the record, returning the schema version that was matched */
+    pub fn validate(
+        &self,
+        expected_commitment: Option<&Commitment>,
+        owner_pubkey: Option<&PublicKey>,
+    ) -> anyhow::Result<WebRecordVersion> {
+        let version = self.version()
+            .ok_or_else(|| anyhow::anyhow!("version number missing or unsupported"))?;
         if let Some(expected_commitment) = expected_commitment {
-            if !self.commitment_ok(expected_commitment) {
+            if !self.commitment_ok(expected_commitment)? {
                 anyhow::bail!("commitment does not match expected commitment")
             };
         };
+        if let Some(owner_pubkey) = owner_pubkey {
+            self.verify_signature(owner_pubkey)?;
+        };
+        Ok(version)
+    }
+
+    /** recompute the canonical message (the record with the `"signature"`
+     * field removed) and check it against the record's `"signature"` field,
+     * a hex-encoded compact secp256k1 signature */
+    pub fn verify_signature(&self, pubkey: &PublicKey) -> anyhow::Result<()> {
+        let signature_hex = self.0.get("signature")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| anyhow::anyhow!("record has no signature field"))?;
+        let signature_bytes = decode_hex(signature_hex)?;
+        let signature = Signature::from_compact(&signature_bytes)?;
+        let mut unsigned_record = self.0.clone();
+        unsigned_record.remove("signature");
+        let canonical_utf8 = serde_jcs::to_vec(&unsigned_record)?;
+        let digest = sha256::Hash::hash(&canonical_utf8);
+        let message = Message::from_digest_slice(digest.as_byte_array())?;
+        Secp256k1::verification_only().verify_ecdsa(&message, &signature, pubkey)?;
         Ok(())
     }
 
-    /** query a telegram handle */
+    /** query a telegram handle; present since version 0.0.1 */
     pub fn telegram(&self) -> Option<&str> {
+        self.version()?;
         self.0.get("telegram").and_then(JsonValue::as_str)
     }
 
+    /** the `"introductions"` object; only present from version 0.0.2 onward */
     fn introductions(&self) -> Option<&JsonMap<String, JsonValue>> {
+        if self.version()? < WebRecordVersion::V0_0_2 {
+            return None;
+        }
         self.0.get("introductions").and_then(JsonValue::as_object)
     }
 
-    /** fee is resolved first from telegram-specific,
+    /** fee is resolved first from `platform`-specific,
      * and then from non-specific platform fee */
-    fn introductions_telegram_fee(&self) -> Option<Decimal> {
+    pub fn introduction_fee(&self, platform: &str) -> Option<Decimal> {
         self.introductions().and_then(|introductions| {
-            introductions.get("telegram")
+            introductions.get(platform)
                 .or_else(||introductions.get("fee"))
                 .and_then(JsonValue::as_str)
                 .and_then(|fee| Decimal::from_str(fee).ok())
         })
     }
+
+    /** every platform advertised under `introductions`, paired with its
+     * resolved fee; the generic `"fee"` key is not a platform, and platforms
+     * whose fee is not a decimal string are skipped */
+    pub fn platforms(&self) -> impl Iterator<Item = (&str, Decimal)> + '_ {
+        self.introductions()
+            .into_iter()
+            .flat_map(|introductions| introductions.keys())
+            .filter(|platform| platform.as_str() != "fee")
+            .filter_map(move |platform| {
+                self.introduction_fee(platform).map(|fee| (platform.as_str(), fee))
+            })
+    }
+
+    /** fee is resolved first from telegram-specific,
+     * and then from non-specific platform fee */
+    fn introductions_telegram_fee(&self) -> Option<Decimal> {
+        self.introduction_fee("telegram")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commitment_hex_round_trip() {
+        let commitment = Commitment([0x11; 32]);
+        let parsed: Commitment = commitment.to_string().parse().unwrap();
+        assert_eq!(commitment, parsed);
+    }
+
+    #[test]
+    fn bitname_info_deserializes_hex_commitment() {
+        let hex_commitment = "11".repeat(32);
+        let json = format!(
+            r#"{{"commitment":"{hex_commitment}","ip4_addr":null,"ip6_addr":null,"owner_pubkey":null}}"#
+        );
+        let info: BitnameInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(info.commitment, Some(Commitment([0x11; 32])));
+    }
+
+    fn encode_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    /** sign `record` (without a `"signature"` field) and insert the
+     * resulting hex-encoded compact signature, returning the signing
+     * keypair's public key */
+    fn sign_record(
+        secp: &Secp256k1<secp256k1::All>,
+        secret_key: &secp256k1::SecretKey,
+        record: &mut JsonMap<String, JsonValue>,
+    ) -> PublicKey {
+        let canonical_utf8 = serde_jcs::to_vec(record).unwrap();
+        let digest = sha256::Hash::hash(&canonical_utf8);
+        let message = Message::from_digest_slice(digest.as_byte_array()).unwrap();
+        let signature = secp.sign_ecdsa(&message, secret_key);
+        record.insert(
+            "signature".to_string(),
+            JsonValue::String(encode_hex(&signature.serialize_compact())),
+        );
+        PublicKey::from_secret_key(secp, secret_key)
+    }
+
+    #[test]
+    fn verify_signature_accepts_matching_pubkey() {
+        let secp = Secp256k1::new();
+        let secret_key = secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let mut map = JsonMap::new();
+        map.insert("version".to_string(), JsonValue::String("0.0.1".to_string()));
+        map.insert("telegram".to_string(), JsonValue::String("alice".to_string()));
+        let public_key = sign_record(&secp, &secret_key, &mut map);
+
+        let record = WebRecord(map);
+        record.verify_signature(&public_key).unwrap();
+    }
+
+    #[test]
+    fn verify_signature_rejects_tampered_record() {
+        let secp = Secp256k1::new();
+        let secret_key = secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let mut map = JsonMap::new();
+        map.insert("version".to_string(), JsonValue::String("0.0.1".to_string()));
+        map.insert("telegram".to_string(), JsonValue::String("alice".to_string()));
+        let public_key = sign_record(&secp, &secret_key, &mut map);
+
+        map.insert("telegram".to_string(), JsonValue::String("mallory".to_string()));
+        let tampered_record = WebRecord(map);
+        assert!(tampered_record.verify_signature(&public_key).is_err());
+    }
+
+    #[test]
+    fn verify_signature_rejects_wrong_pubkey() {
+        let secp = Secp256k1::new();
+        let secret_key = secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let mut map = JsonMap::new();
+        map.insert("version".to_string(), JsonValue::String("0.0.1".to_string()));
+        map.insert("telegram".to_string(), JsonValue::String("alice".to_string()));
+        sign_record(&secp, &secret_key, &mut map);
+
+        let other_secret_key = secp256k1::SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let other_public_key = PublicKey::from_secret_key(&secp, &other_secret_key);
+        let record = WebRecord(map);
+        assert!(record.verify_signature(&other_public_key).is_err());
+    }
+
+    #[test]
+    fn web_record_version_parses_known_versions() {
+        assert_eq!("0.0.1".parse::<WebRecordVersion>().unwrap(), WebRecordVersion::V0_0_1);
+        assert_eq!("0.0.2".parse::<WebRecordVersion>().unwrap(), WebRecordVersion::V0_0_2);
+        assert!("9.9.9".parse::<WebRecordVersion>().is_err());
+    }
+
+    #[test]
+    fn web_record_version_orders_newer_above_older() {
+        assert!(WebRecordVersion::V0_0_1 < WebRecordVersion::V0_0_2);
+        assert_eq!(WebRecordVersion::latest(), WebRecordVersion::V0_0_2);
+    }
+
+    /** a [`Resolver`] that serves a fixed, in-memory record instead of
+     * reaching the network, to exercise `validate`'s wiring */
+    struct MockResolver {
+        record: JsonMap<String, JsonValue>,
+    }
+
+    #[async_trait]
+    impl Resolver for MockResolver {
+        async fn resolve(&self, info: &BitnameInfo) -> anyhow::Result<WebRecord> {
+            let record = WebRecord(self.record.clone());
+            record.validate(info.commitment.as_ref(), info.owner_pubkey.as_ref())?;
+            Ok(record)
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_succeeds_with_matching_commitment() {
+        let mut map = JsonMap::new();
+        map.insert("version".to_string(), JsonValue::String("0.0.1".to_string()));
+        let commitment = WebRecord(map.clone()).commitment().unwrap();
+        let resolver = MockResolver { record: map };
+        let info = BitnameInfo {
+            commitment: Some(commitment),
+            ip4_addr: None,
+            ip6_addr: None,
+            owner_pubkey: None,
+        };
+
+        resolver.resolve(&info).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn resolve_fails_with_wrong_commitment() {
+        let mut map = JsonMap::new();
+        map.insert("version".to_string(), JsonValue::String("0.0.1".to_string()));
+        let resolver = MockResolver { record: map };
+        let info = BitnameInfo {
+            commitment: Some(Commitment([0xff; 32])),
+            ip4_addr: None,
+            ip6_addr: None,
+            owner_pubkey: None,
+        };
+
+        assert!(resolver.resolve(&info).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_fails_with_bad_signature() {
+        let mut map = JsonMap::new();
+        map.insert("version".to_string(), JsonValue::String("0.0.1".to_string()));
+        map.insert("signature".to_string(), JsonValue::String("00".repeat(64)));
+        let resolver = MockResolver { record: map };
+        let secp = Secp256k1::new();
+        let secret_key = secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+        let info = BitnameInfo {
+            commitment: None,
+            ip4_addr: None,
+            ip6_addr: None,
+            owner_pubkey: Some(public_key),
+        };
+
+        assert!(resolver.resolve(&info).await.is_err());
+    }
+
+    fn record_with_introductions() -> WebRecord {
+        let mut introductions = JsonMap::new();
+        introductions.insert("telegram".to_string(), JsonValue::String("1.5".to_string()));
+        introductions.insert("fee".to_string(), JsonValue::String("2.0".to_string()));
+        let mut map = JsonMap::new();
+        map.insert("version".to_string(), JsonValue::String("0.0.2".to_string()));
+        map.insert("introductions".to_string(), JsonValue::Object(introductions));
+        WebRecord(map)
+    }
+
+    #[test]
+    fn introduction_fee_prefers_platform_specific_value() {
+        let record = record_with_introductions();
+        assert_eq!(record.introduction_fee("telegram"), Some(Decimal::from_str("1.5").unwrap()));
+    }
+
+    #[test]
+    fn introduction_fee_falls_back_to_generic_fee() {
+        let record = record_with_introductions();
+        assert_eq!(record.introduction_fee("discord"), Some(Decimal::from_str("2.0").unwrap()));
+    }
+
+    #[test]
+    fn platforms_excludes_generic_fee_key() {
+        let record = record_with_introductions();
+        let platforms: Vec<_> = record.platforms().collect();
+        assert_eq!(platforms, vec![("telegram", Decimal::from_str("1.5").unwrap())]);
+    }
 }
\ No newline at end of file